@@ -89,22 +89,32 @@ use std as std_crate;
 
 use std_crate::{
     char,
-    fmt::{self, Display, Formatter, Write},
-    hint, mem, ptr,
+    fmt::{self, Display, Formatter},
+    ptr, slice,
 };
+#[cfg(not(feature = "std"))]
+use std_crate::fmt::Write;
+#[cfg(feature = "std")]
+use std_crate::{convert::TryFrom, error::Error, io, string::String};
 #[cfg(feature = "std")]
-use std_crate::{convert::TryFrom, error::Error, io};
+use winapi::{shared::minwindef::HMODULE, um::winbase::FORMAT_MESSAGE_FROM_HMODULE};
 use winapi::{
-    shared::minwindef::DWORD,
+    shared::minwindef::{DWORD, LPCVOID},
     um::{
-        errhandlingapi::GetLastError,
+        errhandlingapi::{GetLastError, SetLastError},
         winbase::{
-            FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
-            FORMAT_MESSAGE_MAX_WIDTH_MASK,
+            FormatMessageW, LocalFree, FORMAT_MESSAGE_ALLOCATE_BUFFER, FORMAT_MESSAGE_FROM_SYSTEM,
+            FORMAT_MESSAGE_IGNORE_INSERTS, FORMAT_MESSAGE_MAX_WIDTH_MASK,
         },
-        winnt::WCHAR,
+        winnt::{LPWSTR, WCHAR},
     },
 };
+#[cfg(feature = "std")]
+use winapi::shared::winerror::{
+    ERROR_ACCESS_DENIED, ERROR_ALREADY_EXISTS, ERROR_BROKEN_PIPE, ERROR_FILE_EXISTS,
+    ERROR_FILE_NOT_FOUND, ERROR_INVALID_DRIVE, ERROR_INVALID_PARAMETER, ERROR_NO_DATA,
+    ERROR_OPERATION_ABORTED, ERROR_PATH_NOT_FOUND, ERROR_SHARING_VIOLATION, WAIT_TIMEOUT,
+};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[must_use = "this `W32Error` is unhandled"]
@@ -169,6 +179,25 @@ impl W32Error {
         Self::new(unsafe { GetLastError() })
     }
 
+    /// Sets this error as the last-error code for the calling thread.
+    ///
+    /// This is equivalent to calling the Windows API function `SetLastError` with the wrapped
+    /// error code. It is the symmetric counterpart to `W32Error::last_thread_error`, and is
+    /// mainly useful for staging a known error before invoking code that calls `GetLastError`,
+    /// such as test harnesses and FFI shims for Windows APIs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use w32_error::W32Error;
+    /// let error = W32Error::new(123);
+    /// error.set_as_last_thread_error();
+    /// assert_eq!(W32Error::last_thread_error(), error);
+    /// ```
+    pub fn set_as_last_thread_error(self) {
+        unsafe { SetLastError(self.into_inner()) }
+    }
+
     /// Returns the underlying error code wrapped by a `W32Error`.
     ///
     /// # Examples
@@ -180,51 +209,209 @@ impl W32Error {
     pub const fn into_inner(self) -> DWORD {
         self.0
     }
+
+    #[cfg(feature = "std")]
+    /// Returns the `io::ErrorKind` that most closely corresponds to this error code.
+    ///
+    /// This mirrors the classification that libstd performs for OS error codes on Windows,
+    /// letting callers pattern-match on a portable `io::ErrorKind` without going through an
+    /// `io::Error` conversion first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use w32_error::W32Error;
+    /// use std::io::ErrorKind;
+    /// use winapi::shared::winerror::ERROR_FILE_NOT_FOUND;
+    /// assert_eq!(W32Error::new(ERROR_FILE_NOT_FOUND).kind(), ErrorKind::NotFound);
+    /// ```
+    pub fn kind(self) -> io::ErrorKind {
+        match self.0 {
+            ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION => io::ErrorKind::PermissionDenied,
+            ERROR_ALREADY_EXISTS | ERROR_FILE_EXISTS => io::ErrorKind::AlreadyExists,
+            ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND | ERROR_INVALID_DRIVE => {
+                io::ErrorKind::NotFound
+            }
+            ERROR_BROKEN_PIPE | ERROR_NO_DATA => io::ErrorKind::BrokenPipe,
+            ERROR_INVALID_PARAMETER => io::ErrorKind::InvalidInput,
+            ERROR_OPERATION_ABORTED => io::ErrorKind::Interrupted,
+            WAIT_TIMEOUT => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::Other,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns the decoded, trimmed message for this error, as produced by the system message
+    /// table in the thread's default language.
+    ///
+    /// This is the same text that `Display` prints, but as an owned `String` rather than through
+    /// a `Formatter`, which is more convenient for callers that want to log, serialize, or compare
+    /// the message directly. Returns `None` if `FormatMessageW` does not have a message for this
+    /// error code, which is distinct from the `{:#08X}` fallback that `Display` prints in that
+    /// case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use w32_error::W32Error;
+    /// let message = W32Error::new(0).message();
+    /// assert!(message.is_some());
+    /// ```
+    pub fn message(self) -> Option<String> {
+        self.format_message_for_language(0)
+    }
+
+    #[cfg(feature = "std")]
+    /// Renders this error's message from the system message table in the given language,
+    /// ignoring the thread's default language.
+    ///
+    /// `lang_id` should be constructed with `MAKELANGID`, e.g.
+    /// `MAKELANGID(LANG_ENGLISH, SUBLANG_ENGLISH_US)`. Returns `None` if `FormatMessageW` does
+    /// not have a message for this error code in the requested language.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use w32_error::W32Error;
+    /// let message = W32Error::new(0).format_message_for_language(0x0409);
+    /// assert!(message.is_some());
+    /// ```
+    pub fn format_message_for_language(self, lang_id: DWORD) -> Option<String> {
+        let (wide_buffer, len) =
+            call_format_message(FORMAT_MESSAGE_FROM_SYSTEM, ptr::null(), lang_id, self.0)?;
+        let message = trimmed_message_string(unsafe { slice::from_raw_parts(wide_buffer, len) });
+        let _ = unsafe { LocalFree(wide_buffer.cast()) };
+        Some(message)
+    }
+
+    #[cfg(feature = "std")]
+    /// Renders this error's message using the message table embedded in `module`, falling back
+    /// to the system message table if `module` has no message for this error code.
+    ///
+    /// `module` is typically obtained via `GetModuleHandle` or `LoadLibrary` for the DLL that
+    /// owns the error code, e.g. `netmsg.dll` for network errors or a third-party driver's DLL.
+    /// Returns `None` if neither lookup produces a message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use w32_error::W32Error;
+    /// use std::ptr;
+    /// let message = W32Error::new(0).format_message_from_module(ptr::null_mut());
+    /// assert!(message.is_some());
+    /// ```
+    pub fn format_message_from_module(self, module: HMODULE) -> Option<String> {
+        call_format_message(FORMAT_MESSAGE_FROM_HMODULE, module.cast(), 0, self.0)
+            .or_else(|| call_format_message(FORMAT_MESSAGE_FROM_SYSTEM, ptr::null(), 0, self.0))
+            .map(|(wide_buffer, len)| {
+                let message =
+                    trimmed_message_string(unsafe { slice::from_raw_parts(wide_buffer, len) });
+                let _ = unsafe { LocalFree(wide_buffer.cast()) };
+                message
+            })
+    }
+}
+
+/// Calls `FormatMessageW` with the given source flag/handle and language, returning a pointer to
+/// an OS-allocated buffer together with its length in UTF-16 code units.
+///
+/// Returns `None` if `FormatMessageW` fails to produce a message. The caller is responsible for
+/// freeing the returned buffer with `LocalFree`.
+fn call_format_message(
+    source_flags: DWORD,
+    source: LPCVOID,
+    lang_id: DWORD,
+    code: DWORD,
+) -> Option<(LPWSTR, usize)> {
+    let mut wide_buffer: LPWSTR = ptr::null_mut();
+    let len = unsafe {
+        FormatMessageW(
+            source_flags
+                | FORMAT_MESSAGE_ALLOCATE_BUFFER
+                | FORMAT_MESSAGE_IGNORE_INSERTS
+                | FORMAT_MESSAGE_MAX_WIDTH_MASK,
+            source,
+            code,
+            lang_id,
+            (&mut wide_buffer as *mut LPWSTR).cast(),
+            0,
+            ptr::null_mut(),
+        ) as usize
+    };
+    if len == 0 || wide_buffer.is_null() {
+        None
+    } else {
+        Some((wide_buffer, len))
+    }
+}
+
+/// Decodes a UTF-16 buffer returned by `FormatMessageW` into `char`s, replacing unpaired
+/// surrogates with the Unicode replacement character.
+fn decode_message(wide: &[WCHAR]) -> impl Iterator<Item = char> + '_ {
+    char::decode_utf16(wide.iter().copied()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+}
+
+/// Finds the index of the first and last non-whitespace `char` in a decoded message.
+///
+/// If `FormatMessage` is instructed to strip inserts and manual line breaks from the message,
+/// they may be replaced with whitespace, so the trimming happens here rather than on the raw
+/// `FormatMessage` output.
+fn trimmed_bounds(wide: &[WCHAR]) -> Option<(usize, usize)> {
+    decode_message(wide)
+        .enumerate()
+        .filter(|(_, c)| !c.is_whitespace())
+        .fold(None, |bounds: Option<(usize, usize)>, (i, _)| {
+            Some(bounds.map_or((i, i), |(first, _)| (first, i)))
+        })
+}
+
+#[cfg(not(feature = "std"))]
+/// Writes the portion of `wide` that remains after stripping leading and trailing whitespace.
+fn write_trimmed_message(f: &mut Formatter<'_>, wide: &[WCHAR]) -> fmt::Result {
+    if let Some((first, last)) = trimmed_bounds(wide) {
+        for (_, c) in decode_message(wide).enumerate().take(last + 1).skip(first) {
+            f.write_char(c)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+/// Builds the portion of `wide` that remains after stripping leading and trailing whitespace.
+fn trimmed_message_string(wide: &[WCHAR]) -> String {
+    trimmed_bounds(wide).map_or_else(String::new, |(first, last)| {
+        decode_message(wide)
+            .enumerate()
+            .take(last + 1)
+            .skip(first)
+            .map(|(_, c)| c)
+            .collect()
+    })
 }
 
 impl Display for W32Error {
+    #[cfg(feature = "std")]
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        const MAX_CHARACTERS: u16 = 1024;
-        // According to the MSDN documentation for `FormatMessage`, `wide_buffer` cannot be larger
-        // than 64KB.
-        debug_assert!(mem::size_of::<[WCHAR; MAX_CHARACTERS as _]>() <= 65536);
-        let mut wide_buffer = [WCHAR::default(); MAX_CHARACTERS as _];
-        let len = unsafe {
-            FormatMessageW(
-                FORMAT_MESSAGE_FROM_SYSTEM
-                    | FORMAT_MESSAGE_IGNORE_INSERTS
-                    | FORMAT_MESSAGE_MAX_WIDTH_MASK,
-                ptr::null(),
-                self.0,
-                0,
-                wide_buffer.as_mut_ptr(),
-                MAX_CHARACTERS.into(),
-                ptr::null_mut(),
-            ) as usize
-        };
-        if len == 0 {
+        match self.message() {
+            Some(message) => f.write_str(&message),
             // `FormatMessage` failed. Write out the error code itself as a last resort.
-            f.write_fmt(format_args!("{:#08X}", self.0))
-        } else {
-            // Strip leading and trailing whitespace from the error message.
-            // If `FormatMessage` is instructed to strip inserts and manual line breaks from the
-            // message, they may be replaced with whitespace.
-            let mut char_buffer = [char::default(); MAX_CHARACTERS as _];
-            let char_msg = &mut char_buffer[..len];
-            let wide_msg = &wide_buffer[..len];
-            char::decode_utf16(wide_msg.iter().copied())
-                .zip(char_msg.iter_mut())
-                .for_each(|(src, dst)| *dst = src.unwrap_or(char::REPLACEMENT_CHARACTER));
-            if let Some(a) = char_msg.iter().position(|c| !c.is_whitespace()) {
-                let b = char_msg
-                    .iter()
-                    .rposition(|c| !c.is_whitespace())
-                    .unwrap_or_else(|| unsafe { hint::unreachable_unchecked() });
-                for &c in &char_msg[a..=b] {
-                    f.write_char(c)?;
-                }
+            None => f.write_fmt(format_args!("{:#08X}", self.0)),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match call_format_message(FORMAT_MESSAGE_FROM_SYSTEM, ptr::null(), 0, self.0) {
+            None => {
+                // `FormatMessage` failed. Write out the error code itself as a last resort.
+                f.write_fmt(format_args!("{:#08X}", self.0))
+            }
+            Some((wide_buffer, len)) => {
+                let result =
+                    write_trimmed_message(f, unsafe { slice::from_raw_parts(wide_buffer, len) });
+                let _ = unsafe { LocalFree(wide_buffer.cast()) };
+                result
             }
-            Ok(())
         }
     }
 }